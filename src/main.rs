@@ -1,6 +1,11 @@
 #![feature(naked_functions)]
 use core::arch::asm;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
 
+// default per-task stack size and thread cap used by `RunTime::new`; both are now
+// configurable through `RunTime::with_config`.
 const STACK_SIZE: usize = 1024 * 1024 * 4;
 const THREAD_SIZE: usize = 4;
 static mut RUNTIME: usize = 0;
@@ -8,6 +13,14 @@ static mut RUNTIME: usize = 0;
 struct RunTime {
     threads: Vec<Thread>,
     current: usize,
+    // upper bound on how many task slots may exist; the vector grows on demand.
+    max_threads: usize,
+    // size in bytes of each lazily-allocated task stack.
+    stack_size: usize,
+    // channel id -> index of the task parked waiting to receive on it.
+    waiters: HashMap<usize, usize>,
+    // monotonic source of channel ids.
+    next_channel_id: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -15,27 +28,61 @@ enum ThreadState {
     Available,
     Running,
     Ready,
+    // parked waiting on a channel; the scheduler skips it until it is woken.
+    Blocked,
 }
 
 struct Thread {
     id: usize,
-    // stack should not move to other memory address
-    stack: *mut [u8],
+    // Stack is allocated lazily when a task is scheduled onto this slot and freed
+    // again when the task finishes, so an idle slot costs nothing. `None` means no
+    // stack is currently mapped (also the case for the base thread, which runs on
+    // the real OS stack). The allocation must not move once a task owns it.
+    stack: Option<*mut [u8]>,
     ctx: ThreadContext,
     state: ThreadState,
+    // boxed closure to run; the trampoline's `call_task` shim takes it out of this
+    // slot and calls it on the task's own stack.
+    task: Option<Box<dyn FnOnce()>>,
 }
 
 impl Drop for Thread {
     fn drop(&mut self) {
-        unsafe {
-            // convert stack back to Box, then drop
-            Box::from_raw(self.stack);
-            println!("thread {} exit", self.id);
-        }
+        self.free_stack();
+        println!("thread {} exit", self.id);
     }
 }
 
+// size of the guard page carved out at the bottom of every task stack of at least
+// two pages.
+#[cfg(unix)]
+const PAGE_SIZE: usize = 4096;
+#[cfg(unix)]
+const PROT_NONE: i32 = 0x0;
+#[cfg(unix)]
+const PROT_READ: i32 = 0x1;
+#[cfg(unix)]
+const PROT_WRITE: i32 = 0x2;
+
+#[cfg(unix)]
+extern "C" {
+    fn mprotect(addr: *mut core::ffi::c_void, len: usize, prot: i32) -> i32;
+}
+
+// `mprotect` the lowest page of `stack` to `prot`. The heap allocation is not
+// page-aligned, so we round the base pointer up to the next page boundary and
+// protect the first whole page inside the allocation. Setting `PROT_NONE` turns a
+// stack overflow into an immediate fault instead of silent corruption of whatever
+// heap object happens to sit just below the stack.
+#[cfg(unix)]
+unsafe fn protect_guard_page(stack: *mut [u8], prot: i32) {
+    let base = stack as *mut u8 as usize;
+    let guard = (base + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    mprotect(guard as *mut core::ffi::c_void, PAGE_SIZE, prot);
+}
+
 // callee saved register should store carefully
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
 #[repr(C)]
 #[derive(Default, Debug)]
 struct ThreadContext {
@@ -48,28 +95,139 @@ struct ThreadContext {
     rbp: usize,
 }
 
+// Windows keeps more registers non-volatile than the System V ABI: rdi and rsi
+// are callee-saved, and xmm6-xmm15 must be preserved across calls. Each xmm slot
+// is stored as `[u64; 2]` so the struct only needs 8-byte field alignment for the
+// integer registers, but the xmm slots still have to land on 16-byte boundaries
+// because `movaps` faults on an unaligned memory operand. `align(16)` on the
+// struct plus the explicit `_pad` word keeps the first xmm slot at offset 0x50.
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+#[repr(C, align(16))]
+#[derive(Default, Debug)]
+struct ThreadContext {
+    rsp: usize,   // 0x00
+    r15: usize,   // 0x08
+    r14: usize,   // 0x10
+    r13: usize,   // 0x18
+    r12: usize,   // 0x20
+    rbx: usize,   // 0x28
+    rbp: usize,   // 0x30
+    rdi: usize,   // 0x38
+    rsi: usize,   // 0x40
+    _pad: usize,  // 0x48 - keep xmm6 at a 16-byte aligned offset
+    xmm6: [u64; 2],  // 0x50
+    xmm7: [u64; 2],  // 0x60
+    xmm8: [u64; 2],  // 0x70
+    xmm9: [u64; 2],  // 0x80
+    xmm10: [u64; 2], // 0x90
+    xmm11: [u64; 2], // 0xA0
+    xmm12: [u64; 2], // 0xB0
+    xmm13: [u64; 2], // 0xC0
+    xmm14: [u64; 2], // 0xD0
+    xmm15: [u64; 2], // 0xE0
+}
+
+// RISC-V keeps its callee-saved set in `ra`/`sp`/`s0..s11`. Unlike x86-64 the
+// return address lives in a register (`ra`) rather than on the stack, so a freshly
+// spawned task can't be bootstrapped by pushing a trampoline word. Instead
+// `spawn_task` seeds `ra` with the `task_return` guard and hands the task entry
+// point to the context through `nra` ("new return address"), which `ctx_switch`
+// loads into `t0` and jumps to (`jr t0`) so the first switch lands in the task.
+#[cfg(target_arch = "riscv64")]
+#[repr(C)]
+#[derive(Default, Debug)]
+struct ThreadContext {
+    ra: usize,   // 0x00 - x1, return address
+    sp: usize,   // 0x08 - x2, stack pointer
+    fp: usize,   // 0x10 - x8, s0/frame pointer
+    s1: usize,   // 0x18 - x9
+    s2: usize,   // 0x20 - x18
+    s3: usize,   // 0x28 - x19
+    s4: usize,   // 0x30 - x20
+    s5: usize,   // 0x38 - x21
+    s6: usize,   // 0x40 - x22
+    s7: usize,   // 0x48 - x23
+    s8: usize,   // 0x50 - x24
+    s9: usize,   // 0x58 - x25
+    s10: usize,  // 0x60 - x26
+    s11: usize,  // 0x68 - x27
+    nra: usize,  // 0x70 - entry address for a freshly spawned task
+}
+
 impl Thread {
+    // Create an empty, stackless slot. The stack is allocated on demand by
+    // `alloc_stack` when a task is scheduled here.
     fn new(id: usize) -> Self {
-        let buff = Box::new([0u8; STACK_SIZE]);
-        // manage stack memory our self
-        let stack = Box::into_raw(buff);
         Self {
             id,
             ctx: Default::default(),
             state: ThreadState::Available,
-            stack,
+            stack: None,
+            task: None,
+        }
+    }
+
+    // Allocate this slot's stack, sized to `stack_size`, and return the raw base
+    // pointer. Guard-page invariant: the lowest page is mapped PROT_NONE so an
+    // overflow faults deterministically; `free_stack` restores it before freeing.
+    // The guard is only installed when the stack is at least two pages, so very
+    // small (kilobyte) stacks are still usable.
+    fn alloc_stack(&mut self, stack_size: usize) -> *mut [u8] {
+        // free any leftover stack from a previous task before reallocating.
+        self.free_stack();
+        let buff = vec![0u8; stack_size].into_boxed_slice();
+        // manage stack memory our self
+        let stack = Box::into_raw(buff);
+        #[cfg(unix)]
+        unsafe {
+            if stack_size >= 2 * PAGE_SIZE {
+                protect_guard_page(stack, PROT_NONE);
+            }
+        }
+        self.stack = Some(stack);
+        stack
+    }
+
+    // Free this slot's stack if one is mapped, restoring the guard page first so
+    // the allocator can safely reuse the memory.
+    fn free_stack(&mut self) {
+        if let Some(stack) = self.stack.take() {
+            unsafe {
+                #[cfg(unix)]
+                {
+                    let len = (*stack).len();
+                    if len >= 2 * PAGE_SIZE {
+                        protect_guard_page(stack, PROT_READ | PROT_WRITE);
+                    }
+                }
+                // convert stack back to Box, then drop
+                Box::from_raw(stack);
+            }
         }
     }
 }
 
 impl RunTime {
     fn new() -> Self {
-        let mut threads = (0..THREAD_SIZE).map(Thread::new).collect::<Vec<_>>();
+        Self::with_config(THREAD_SIZE, STACK_SIZE)
+    }
+
+    // Build a runtime with a custom thread cap and per-task stack size. Only the
+    // base thread (thread 0, running on the real OS stack) is created up front;
+    // further slots are grown on demand in `spawn_task`, and each stack is
+    // allocated lazily, so a trivial workload costs almost nothing.
+    fn with_config(max_threads: usize, stack_size: usize) -> Self {
+        assert!(max_threads >= 1, "need at least the base thread");
+        let mut base = Thread::new(0);
         // mark thread 0 as base thread
-        threads[0].state = ThreadState::Running;
+        base.state = ThreadState::Running;
         Self {
             current: 0,
-            threads,
+            threads: vec![base],
+            max_threads,
+            stack_size,
+            waiters: HashMap::new(),
+            next_channel_id: 0,
         }
     }
 
@@ -80,39 +238,95 @@ impl RunTime {
         }
     }
 
-    // spawn_task take task to run with RunTime threads
-    fn spawn_task(&mut self, task: fn()) {
-        // find available thread
-        let available_thread = self
+    // spawn_task take task to run with RunTime threads. The task is any `FnOnce`
+    // closure returning a value, so it can capture its environment; we box a
+    // wrapper that runs it and stores the result into a shared slot, stash the
+    // wrapper on the thread for the `call_task` shim to pick up once scheduled,
+    // and hand the caller a `JoinHandle` to collect the value later.
+    fn spawn_task<F, T>(&mut self, task: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        // find an available slot, growing the pool on demand up to `max_threads`.
+        let pos = match self
             .threads
-            .iter_mut()
-            .find(|t| t.state == ThreadState::Available)
-            .expect("no available thread to run task");
+            .iter()
+            .position(|t| t.state == ThreadState::Available)
+        {
+            Some(pos) => pos,
+            None => {
+                assert!(
+                    self.threads.len() < self.max_threads,
+                    "no available thread to run task"
+                );
+                let id = self.threads.len();
+                self.threads.push(Thread::new(id));
+                id
+            }
+        };
+        // shared slot the task writes its return value into on completion.
+        let result: Rc<RefCell<Option<T>>> = Rc::new(RefCell::new(None));
+        let slot = result.clone();
+        let stack_size = self.stack_size;
+        let available_thread = &mut self.threads[pos];
+        // allocate this slot's stack lazily, only now that it is actually used.
+        let stack_ptr = available_thread.alloc_stack(stack_size);
+        available_thread.task = Some(Box::new(move || {
+            let value = task();
+            *slot.borrow_mut() = Some(value);
+        }));
         // set up task on available thread
+        #[cfg(target_arch = "x86_64")]
         unsafe {
-            let stack = (&mut (*available_thread.stack)[0]) as *mut u8;
-            // stack address align to 16 bytes
-            let stack_bottom = (stack.add(STACK_SIZE) as usize & !0xFF) as *mut u8;
+            let stack = stack_ptr as *mut u8;
+            // Align the stack bottom to exactly 16 bytes, as the System V/Windows
+            // ABI requires. We push three 8-byte trampoline words and hand `ctx.rsp`
+            // to `ctx_switch`, whose `ret` pops `call_task` and leaves rsp at
+            // `stack_bottom - 24`. Since `stack_bottom` is 16-aligned, that is
+            // `rsp % 16 == 8` at the task prologue -- exactly the "just after a
+            // `call`" state the ABI expects, so any `movaps` the task emits is safe.
+            let stack_bottom = (stack.add(stack_size) as usize & !0xF) as *mut u8;
+            debug_assert_eq!(stack_bottom as usize % 16, 0, "stack bottom must be 16-byte aligned");
             std::ptr::write(stack_bottom.offset(-16) as *mut usize, task_return as usize);
             std::ptr::write(stack_bottom.offset(-24) as *mut usize, just_ret as usize);
-            std::ptr::write(stack_bottom.offset(-32) as *mut usize, task as usize);
+            std::ptr::write(stack_bottom.offset(-32) as *mut usize, call_task as usize);
             available_thread.ctx.rsp = stack_bottom.offset(-32) as usize;
         }
+        // RISC-V passes the return address in `ra` rather than on the stack, so we
+        // seed the context registers directly instead of pushing a trampoline: the
+        // first switch jumps to `nra` (the task), and when the task returns it lands
+        // in `ra`, the `task_return` guard that yields the thread back out.
+        #[cfg(target_arch = "riscv64")]
+        unsafe {
+            let stack = stack_ptr as *mut u8;
+            // align the stack bottom to exactly 16 bytes as the RISC-V ABI requires.
+            let stack_bottom = (stack.add(stack_size) as usize & !0xF) as *mut u8;
+            available_thread.ctx.sp = stack_bottom as usize;
+            available_thread.ctx.ra = task_return as usize;
+            available_thread.ctx.nra = call_task as usize;
+        }
         available_thread.state = ThreadState::Ready;
+        JoinHandle { id: pos, result }
     }
 
     fn yield_out(&mut self) -> bool {
+        // reclaim stacks of finished tasks; safe here because we run on the
+        // current thread's stack, never on the one being freed.
+        self.reclaim();
         let mut pos = self.current;
         while self.threads[pos].state != ThreadState::Ready {
             pos += 1;
-            if pos == THREAD_SIZE {
+            if pos == self.threads.len() {
                 pos = 0;
             }
             if pos == self.current {
                 return false;
             }
         }
-        if self.threads[self.current].state != ThreadState::Available {
+        // only a still-running task goes back into the Ready pool; a task that
+        // parked itself (Blocked) or finished (Available) keeps its state.
+        if self.threads[self.current].state == ThreadState::Running {
             self.threads[self.current].state = ThreadState::Ready;
         }
         let old = self.current;
@@ -130,7 +344,11 @@ impl RunTime {
             // 3. clobber_abi("C") tells the compiler to push the values of these
             //    registers on to the stack before calling ctx_switch and pop
             //    them back in to the same registers once the function returns.
+            #[cfg(target_arch = "x86_64")]
             asm!("call ctx_switch", in("rdi") old_ctx, in("rsi" ) new_ctx, clobber_abi("C"));
+            // RISC-V passes the two context pointers in a0/a1 per its C ABI.
+            #[cfg(target_arch = "riscv64")]
+            asm!("call ctx_switch", in("a0") old_ctx, in("a1") new_ctx, clobber_abi("C"));
         }
         true
     }
@@ -143,13 +361,138 @@ impl RunTime {
 
     fn ret(&mut self) {
         if self.current != 0 {
+            // mark the slot free; its stack is reclaimed by a later `yield_out` made
+            // from another thread (not here, nor in the `yield_out` below, since we
+            // are still executing on that very stack).
             self.threads[self.current].state = ThreadState::Available;
             self.yield_out();
         }
     }
+
+    // Free the stacks of finished (Available) slots that still hold one, skipping
+    // the current thread since we are running on its stack. This is where a
+    // completed task's lazily-allocated stack is actually handed back.
+    fn reclaim(&mut self) {
+        let current = self.current;
+        for (idx, t) in self.threads.iter_mut().enumerate() {
+            if idx != current && t.state == ThreadState::Available && t.stack.is_some() {
+                t.free_stack();
+            }
+        }
+    }
+
+    // hand out a fresh channel id.
+    fn new_channel_id(&mut self) -> usize {
+        let id = self.next_channel_id;
+        self.next_channel_id += 1;
+        id
+    }
+
+    // park the current task on `chan_id`: mark it Blocked, remember it as the
+    // waiter for that channel, and yield so the scheduler moves on.
+    fn block_on(&mut self, chan_id: usize) {
+        self.threads[self.current].state = ThreadState::Blocked;
+        self.waiters.insert(chan_id, self.current);
+        self.yield_out();
+    }
+
+    // wake whatever task is parked on `chan_id`, if any, by marking it Ready.
+    fn wake(&mut self, chan_id: usize) {
+        if let Some(idx) = self.waiters.remove(&chan_id) {
+            if self.threads[idx].state == ThreadState::Blocked {
+                self.threads[idx].state = ThreadState::Ready;
+            }
+        }
+    }
+}
+
+// user-space channel: tasks push/pop values through a shared queue and block
+// cooperatively on the scheduler rather than on an OS primitive.
+fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let id = unsafe {
+        let rt_ptr = RUNTIME as *mut RunTime;
+        (*rt_ptr).new_channel_id()
+    };
+    let queue: Rc<RefCell<VecDeque<T>>> = Rc::new(RefCell::new(VecDeque::new()));
+    (
+        Sender {
+            id,
+            queue: queue.clone(),
+        },
+        Receiver { id, queue },
+    )
+}
+
+// sending half of a channel.
+struct Sender<T> {
+    id: usize,
+    queue: Rc<RefCell<VecDeque<T>>>,
+}
+
+impl<T> Sender<T> {
+    // push a value and wake any task parked waiting to receive on this channel.
+    fn send(&self, value: T) {
+        self.queue.borrow_mut().push_back(value);
+        unsafe {
+            let rt_ptr = RUNTIME as *mut RunTime;
+            (*rt_ptr).wake(self.id);
+        }
+    }
+}
+
+// receiving half of a channel.
+struct Receiver<T> {
+    id: usize,
+    queue: Rc<RefCell<VecDeque<T>>>,
+}
+
+impl<T> Receiver<T> {
+    // take the next value, parking the current task while the channel is empty.
+    fn recv(&self) -> T {
+        loop {
+            if let Some(value) = self.queue.borrow_mut().pop_front() {
+                return value;
+            }
+            unsafe {
+                let rt_ptr = RUNTIME as *mut RunTime;
+                (*rt_ptr).block_on(self.id);
+            }
+        }
+    }
+}
+
+// Handle to a spawned task, used to collect its return value. Holds the target
+// thread index and a clone of the shared result slot the task writes into.
+struct JoinHandle<T> {
+    id: usize,
+    result: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    // Block the calling task until the target finishes, then take its result.
+    // We cannot truly block on a single OS thread, so we cooperatively `yield_out`
+    // until the scheduler marks the target thread `Available` (which `ret` does
+    // once the task returns), and only then read the slot.
+    fn join(self) -> T {
+        loop {
+            let rt = unsafe { &*(RUNTIME as *const RunTime) };
+            if rt.threads[self.id].state == ThreadState::Available {
+                break;
+            }
+            yield_thread();
+        }
+        self.result
+            .borrow_mut()
+            .take()
+            .expect("joined task produced no result")
+    }
+
+    // Abandon the task: nobody collects its result, the slot is just dropped.
+    fn detach(self) {}
 }
 
 // return to next return address
+#[cfg(target_arch = "x86_64")]
 #[naked]
 unsafe extern "C" fn just_ret() {
     asm!("ret", options(noreturn))
@@ -157,6 +500,39 @@ unsafe extern "C" fn just_ret() {
 
 // callee saved registers should be save to thread local variables
 // and restore new thread context to run.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+#[naked]
+#[no_mangle]
+extern "C" fn ctx_switch() {
+    unsafe {
+        asm!(
+            "mov [rdi + 0x00], rsp",
+            "mov [rdi + 0x08], r15",
+            "mov [rdi + 0x10], r14",
+            "mov [rdi + 0x18], r13",
+            "mov [rdi + 0x20], r12",
+            "mov [rdi + 0x28], rbx",
+            "mov [rdi + 0x30], rbp",
+            "mov rsp, [rsi + 0x00]",
+            "mov r15, [rsi + 0x08]",
+            "mov r14, [rsi + 0x10]",
+            "mov r13, [rsi + 0x18]",
+            "mov r12, [rsi + 0x20]",
+            "mov rbx, [rsi + 0x28]",
+            "mov rbp, [rsi + 0x30]",
+            "ret",
+            options(noreturn)
+        );
+    }
+}
+
+// Windows parallel block. The pointers are still passed privately in rdi/rsi (see
+// the `asm!("call ctx_switch", ...)` in `yield_out`), so the argument registers
+// match the System V version; only the saved set differs. The extra non-volatile
+// registers rdi/rsi live at 0x38/0x40 and are loaded last on restore because they
+// double as the base pointers for the whole sequence. The xmm slots sit at
+// 0x50..0xE0 and use `movaps` against the 16-byte aligned `ThreadContext`.
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
 #[naked]
 #[no_mangle]
 extern "C" fn ctx_switch() {
@@ -169,6 +545,18 @@ extern "C" fn ctx_switch() {
             "mov [rdi + 0x20], r12",
             "mov [rdi + 0x28], rbx",
             "mov [rdi + 0x30], rbp",
+            "mov [rdi + 0x38], rdi",
+            "mov [rdi + 0x40], rsi",
+            "movaps [rdi + 0x50], xmm6",
+            "movaps [rdi + 0x60], xmm7",
+            "movaps [rdi + 0x70], xmm8",
+            "movaps [rdi + 0x80], xmm9",
+            "movaps [rdi + 0x90], xmm10",
+            "movaps [rdi + 0xA0], xmm11",
+            "movaps [rdi + 0xB0], xmm12",
+            "movaps [rdi + 0xC0], xmm13",
+            "movaps [rdi + 0xD0], xmm14",
+            "movaps [rdi + 0xE0], xmm15",
             "mov rsp, [rsi + 0x00]",
             "mov r15, [rsi + 0x08]",
             "mov r14, [rsi + 0x10]",
@@ -176,12 +564,90 @@ extern "C" fn ctx_switch() {
             "mov r12, [rsi + 0x20]",
             "mov rbx, [rsi + 0x28]",
             "mov rbp, [rsi + 0x30]",
+            "movaps xmm6, [rsi + 0x50]",
+            "movaps xmm7, [rsi + 0x60]",
+            "movaps xmm8, [rsi + 0x70]",
+            "movaps xmm9, [rsi + 0x80]",
+            "movaps xmm10, [rsi + 0x90]",
+            "movaps xmm11, [rsi + 0xA0]",
+            "movaps xmm12, [rsi + 0xB0]",
+            "movaps xmm13, [rsi + 0xC0]",
+            "movaps xmm14, [rsi + 0xD0]",
+            "movaps xmm15, [rsi + 0xE0]",
+            // rdi/rsi double as the base pointers, so restore them last.
+            "mov rdi, [rsi + 0x38]",
+            "mov rsi, [rsi + 0x40]",
             "ret",
             options(noreturn)
         );
     }
 }
 
+// RISC-V context switch. The old registers are stored through the pointer in a0
+// and the new ones loaded from a1. The live `ra` is mirrored into both the `ra`
+// and `nra` slots so that a resumed task jumps straight back to where it yielded,
+// while a freshly spawned task (whose `nra` we seeded with the entry point in
+// `spawn_task`) jumps into the task body with the `task_return` guard sitting in
+// `ra`. The final `jr t0` dispatches to `nra`.
+#[cfg(target_arch = "riscv64")]
+#[naked]
+#[no_mangle]
+extern "C" fn ctx_switch() {
+    unsafe {
+        asm!(
+            "sd ra, 0x00(a0)",
+            "sd sp, 0x08(a0)",
+            "sd s0, 0x10(a0)",
+            "sd s1, 0x18(a0)",
+            "sd s2, 0x20(a0)",
+            "sd s3, 0x28(a0)",
+            "sd s4, 0x30(a0)",
+            "sd s5, 0x38(a0)",
+            "sd s6, 0x40(a0)",
+            "sd s7, 0x48(a0)",
+            "sd s8, 0x50(a0)",
+            "sd s9, 0x58(a0)",
+            "sd s10, 0x60(a0)",
+            "sd s11, 0x68(a0)",
+            // a resumed task must re-enter at its saved ra, so mirror it into nra.
+            "sd ra, 0x70(a0)",
+            "ld sp, 0x08(a1)",
+            "ld s0, 0x10(a1)",
+            "ld s1, 0x18(a1)",
+            "ld s2, 0x20(a1)",
+            "ld s3, 0x28(a1)",
+            "ld s4, 0x30(a1)",
+            "ld s5, 0x38(a1)",
+            "ld s6, 0x40(a1)",
+            "ld s7, 0x48(a1)",
+            "ld s8, 0x50(a1)",
+            "ld s9, 0x58(a1)",
+            "ld s10, 0x60(a1)",
+            "ld s11, 0x68(a1)",
+            "ld ra, 0x00(a1)",
+            "ld t0, 0x70(a1)",
+            "jr t0",
+            options(noreturn)
+        );
+    }
+}
+
+// generic entry shim written as the trampoline target for every spawned task.
+// It runs on the freshly switched-in task stack, takes the boxed closure out of
+// the current thread's slot and calls it; when it returns control falls through
+// to the `task_return` trampoline word that yields the thread out.
+extern "C" fn call_task() {
+    unsafe {
+        let rt_ptr = RUNTIME as *mut RunTime;
+        let current = (*rt_ptr).current;
+        let task = (*rt_ptr).threads[current]
+            .task
+            .take()
+            .expect("scheduled thread has no task to run");
+        task();
+    }
+}
+
 // when task return, we should do thread yield
 pub fn task_return() {
     unsafe {
@@ -201,21 +667,29 @@ pub fn yield_thread() {
 fn main() {
     let mut rt = RunTime::new();
     rt.init();
-    rt.spawn_task(|| {
-        let task_id = 1;
+    let (tx, rx) = channel::<usize>();
+    // producer feeds values into the channel, yielding between each send.
+    let producer = rt.spawn_task(move || {
         for i in 0..10 {
-            println!("in task: {}, conter: {}", task_id, i);
+            println!("producer sending {}", i);
+            tx.send(i);
             yield_thread();
         }
-        println!("in task: {}, finished", task_id);
+        println!("producer finished");
     });
-    rt.spawn_task(|| {
-        let task_id = 2;
-        for i in 0..10 {
-            println!("in task: {}, conter: {}", task_id, i);
-            yield_thread();
+    // consumer blocks on recv and sums the values it gets, returning the total.
+    let consumer = rt.spawn_task(move || {
+        let mut sum = 0;
+        for _ in 0..10 {
+            let v = rx.recv();
+            println!("consumer received {}", v);
+            sum += v;
         }
-        println!("in task: {}, finished", task_id);
+        sum
     });
     rt.run();
+    // nobody collects the producer; detach it and let its result be dropped.
+    producer.detach();
+    // collect the consumer's total now that it has run to completion.
+    println!("consumer total = {}", consumer.join());
 }